@@ -0,0 +1,222 @@
+//! `wsload`: a WebSocket load generator for benchmarking the chamber.
+//!
+//! Opens a configurable number of concurrent connections to a chamber's
+//! `/ws` endpoint, has each send [`chamber::protocol::ClientMsg::Chat`]
+//! messages at a fixed rate for a fixed duration, and reports aggregate
+//! throughput and round-trip latency. Round-trip latency is measured from
+//! the moment a message is sent to the moment that connection observes
+//! its own echo come back over the broadcast channel, so it captures
+//! queueing delay under the shared `broadcast::channel(100)` as well as
+//! network time.
+//!
+//! ```bash
+//! wsload --url ws://localhost:3000/ws --connections 50 --rate 10 --duration-secs 20
+//! ```
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use chamber::protocol::{ClientMsg, ServerMsg};
+use clap::Parser;
+use futures_util::{SinkExt, StreamExt};
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+#[derive(Parser)]
+struct Args {
+    /// Chamber WebSocket endpoint to load-test
+    #[arg(long, default_value = "ws://localhost:3000/ws")]
+    url: String,
+    /// Number of concurrent connections to open
+    #[arg(long, default_value_t = 10)]
+    connections: usize,
+    /// Messages per second sent by each connection
+    #[arg(long, default_value_t = 10.0)]
+    rate: f64,
+    /// How long each connection keeps sending, in seconds
+    #[arg(long, default_value_t = 10)]
+    duration_secs: u64,
+    /// Size, in bytes, of each chat message's body
+    #[arg(long, default_value_t = 64)]
+    payload_size: usize,
+}
+
+/// Outcome of a single connection's run.
+#[derive(Default)]
+struct ConnReport {
+    sent: u64,
+    received: u64,
+    /// Round-trip latencies for messages that did come back
+    latencies: Vec<Duration>,
+    /// The connection failed to establish or errored out mid-run
+    dropped: bool,
+    /// The peer closed the connection before the run finished
+    closed: bool,
+}
+
+/// Drive one connection for `duration`, reporting its outcome on `tx`.
+async fn run_connection(
+    url: String,
+    rate: f64,
+    duration: Duration,
+    payload_size: usize,
+    tx: mpsc::UnboundedSender<ConnReport>,
+) {
+    let mut report = ConnReport::default();
+
+    let (stream, _) = match tokio_tungstenite::connect_async(&url).await {
+        Ok(v) => v,
+        Err(e) => {
+            tracing::warn!("connect failed: {}", e);
+            report.dropped = true;
+            let _ = tx.send(report);
+            return;
+        }
+    };
+    let (mut write, mut read) = stream.split();
+
+    // The chamber greets us with a Welcome before anything else; we need
+    // our own id to recognize our own echoes coming back.
+    let my_id = loop {
+        match read.next().await {
+            Some(Ok(WsMessage::Text(t))) => match serde_json::from_str::<ServerMsg>(&t) {
+                Ok(ServerMsg::Welcome { id }) => break id,
+                _ => continue,
+            },
+            _ => {
+                report.dropped = true;
+                let _ = tx.send(report);
+                return;
+            }
+        }
+    };
+
+    let body = "x".repeat(payload_size);
+    // In-flight sends, oldest first; the broadcast channel preserves a
+    // single sender's ordering, so each echo is matched with the oldest
+    // pending send. A message that never echoes (refused by the chamber's
+    // rate limiter, or skipped when this connection lags) is not detected:
+    // it stays pending and every later echo is matched one send too early.
+    let mut pending: VecDeque<Instant> = VecDeque::new();
+
+    let deadline = Instant::now() + duration;
+    let mut ticker = tokio::time::interval(Duration::from_secs_f64(1.0 / rate));
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        let now = Instant::now();
+        if now >= deadline && pending.is_empty() {
+            break;
+        }
+
+        tokio::select! {
+            _ = ticker.tick(), if now < deadline => {
+                let msg = ClientMsg::Chat { body: body.clone() };
+                let text = serde_json::to_string(&msg).expect("ClientMsg always serializes");
+                if write.send(WsMessage::Text(text)).await.is_err() {
+                    report.dropped = true;
+                    break;
+                }
+                pending.push_back(Instant::now());
+                report.sent += 1;
+            }
+            msg = read.next() => {
+                match msg {
+                    Some(Ok(WsMessage::Text(t))) => {
+                        if let Ok(ServerMsg::Chat { from, .. }) = serde_json::from_str::<ServerMsg>(&t) {
+                            if from == my_id {
+                                if let Some(start) = pending.pop_front() {
+                                    report.latencies.push(start.elapsed());
+                                    report.received += 1;
+                                }
+                            }
+                        }
+                    }
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => {
+                        tracing::warn!("{} read error: {}", my_id, e);
+                        report.dropped = true;
+                        break;
+                    }
+                    None => {
+                        report.closed = true;
+                        break;
+                    }
+                }
+            }
+            _ = tokio::time::sleep(Duration::from_millis(50)), if now >= deadline => {
+                // Draining: give outstanding echoes a little more time,
+                // then give up on them.
+                break;
+            }
+        }
+    }
+
+    let _ = tx.send(report);
+}
+
+/// The `p`-th percentile (0.0..=1.0) of an already-sorted slice.
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let idx = (((sorted.len() - 1) as f64) * p).round() as usize;
+    sorted[idx]
+}
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt::init();
+    let args = Args::parse();
+    let duration = Duration::from_secs(args.duration_secs);
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut handles = Vec::with_capacity(args.connections);
+    for _ in 0..args.connections {
+        let url = args.url.clone();
+        let tx = tx.clone();
+        handles.push(tokio::spawn(run_connection(
+            url,
+            args.rate,
+            duration,
+            args.payload_size,
+            tx,
+        )));
+    }
+    drop(tx);
+
+    let mut reports = Vec::with_capacity(args.connections);
+    while let Some(report) = rx.recv().await {
+        reports.push(report);
+    }
+    for h in handles {
+        let _ = h.await;
+    }
+
+    let total_sent: u64 = reports.iter().map(|r| r.sent).sum();
+    let total_received: u64 = reports.iter().map(|r| r.received).sum();
+    let dropped = reports.iter().filter(|r| r.dropped).count();
+    let closed = reports.iter().filter(|r| r.closed).count();
+    let lagged = total_sent.saturating_sub(total_received);
+
+    let mut latencies: Vec<Duration> = reports
+        .iter()
+        .flat_map(|r| r.latencies.iter().copied())
+        .collect();
+    latencies.sort_unstable();
+
+    let throughput = total_received as f64 / duration.as_secs_f64();
+
+    println!("connections:       {}", args.connections);
+    println!("sent:               {}", total_sent);
+    println!("received:           {}", total_received);
+    println!("throughput:         {:.1} msg/s", throughput);
+    println!("p50 latency:        {:?}", percentile(&latencies, 0.50));
+    println!("p95 latency:        {:?}", percentile(&latencies, 0.95));
+    println!("p99 latency:        {:?}", percentile(&latencies, 0.99));
+    println!("lagged (unacked):   {}", lagged);
+    println!("dropped connections:{}", dropped);
+    println!("closed connections: {}", closed);
+}
@@ -0,0 +1,3 @@
+//! Shared types between the chamber server and its tooling (e.g. `wsload`).
+
+pub mod protocol;
@@ -3,17 +3,24 @@
 //!
 //! # Behavior
 //!
+//! Clients and the chamber exchange JSON envelopes (see
+//! [`chamber::protocol`]).
+//!
 //! - A user connects to the server
-//! - Reply: "You are &lt;id&gt;"
-//! - The user sends a message
-//! - Reply (everyone): "&lt;id&gt; says &lt;msg&gt;"
+//! - Reply: [`chamber::protocol::ServerMsg::Welcome`]
+//! - The user sends [`chamber::protocol::ClientMsg::Chat`]
+//! - Reply (everyone): [`chamber::protocol::ServerMsg::Chat`]
 //! - (This includes the user themselves)
-//! - The user sends binary data
-//! - Reply: closing with "only text messages are allowed"
+//! - The user sends [`chamber::protocol::ClientMsg::SetNick`]
+//! - Their displayed nickname changes; no reply
+//! - The user sends [`chamber::protocol::ClientMsg::Whisper`]
+//! - Reply (only the target): [`chamber::protocol::ServerMsg::Chat`]
+//! - The user sends binary data, or JSON that doesn't parse as
+//!   [`chamber::protocol::ClientMsg`]
+//! - Reply: [`chamber::protocol::ServerMsg::Error`], or for binary data, closing
+//!   with "only text messages are allowed"
 //! - The user disconnects
-//! - Reply (everyone): "&lt;id&gt; disconnected"
-//! - The user is set to be receiving binary data
-//! - (Filtered out, not received)
+//! - Reply (everyone): [`chamber::protocol::ServerMsg::UserLeft`]
 //!
 //! # Environment Variables
 //!
@@ -26,123 +33,395 @@
 //!
 //! If not given, the default is "ws://localhost:3000/ws".
 //!
+//! Set "MAX_MSG_BYTES" to cap the size, in bytes, of a fully reassembled
+//! text message, and "MAX_FRAME_BYTES" to additionally cap the size of any
+//! single frame (defaults to the same value as "MAX_MSG_BYTES", itself
+//! defaulting to 500). Both caps are handed to
+//! [`WebSocketUpgrade::max_message_size`]/[`WebSocketUpgrade::max_frame_size`]
+//! so tungstenite stops accepting frames before it ever reassembles an
+//! oversized message, closing the connection with code 1009 ("message too
+//! big").
+//!
+//! Set "RATE_LIMIT_PER_SEC" and "RATE_LIMIT_BURST" to tune the
+//! per-connection token-bucket flood protection (defaults: 5 messages per
+//! second, burst of 10). A client that repeatedly exceeds its allowance is
+//! closed with code 1008.
+//!
+//! Set "OUTBOUND_LOW_WATERMARK", "OUTBOUND_HIGH_WATERMARK", and
+//! "OUTBOUND_STALL_TIMEOUT_MS" to tune the per-connection outbound queue
+//! (defaults: 10, 50, 5000). A connection whose queue stays above the
+//! high watermark for longer than the timeout is closed with code 1013
+//! ("try again later") rather than being left to lag the shared broadcast
+//! channel for everyone else.
+//!
+//! # Shutdown
+//!
+//! SIGINT and SIGTERM trigger a graceful shutdown: the server stops
+//! accepting new connections, every connected client is sent a
+//! [`chamber::protocol::ServerMsg::Shutdown`] and closed with code 1001 ("going
+//! away"), and `main` returns once all connections have drained. Because
+//! `axum` spawns each upgraded connection's task itself (outside of what
+//! `axum::Server::with_graceful_shutdown` waits on), draining is tracked
+//! separately with a `tokio_util::task::TaskTracker`; `main` awaits it
+//! after the server future resolves.
+//!
+//! # Transports
+//!
+//! The chamber's relay logic (`handle`, below) never talks to
+//! `axum::extract::ws` directly; it's written against the [`Transport`]
+//! trait. WebSocket is the only transport wired up today, but a second
+//! backend (e.g. WebTransport) can be registered on its own route in
+//! [`route`] without touching `handle` at all. See [`transport`].
+//!
+//! # Benchmarking
+//!
+//! The `wsload` binary opens many concurrent connections and reports
+//! throughput and round-trip latency against a running chamber; see
+//! `src/bin/wsload.rs`.
+//!
 //! (See the implementation of [`route`] for more details.)
 
+mod transport;
+
 use std::{
+    collections::{HashMap, VecDeque},
     sync::{
         atomic::{self, AtomicU64},
-        Arc,
+        Arc, Mutex,
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use axum::{
     debug_handler,
     extract::{
-        ws::{CloseFrame, Message, WebSocket},
+        ws::Message,
         State, WebSocketUpgrade,
     },
     response::{Html, IntoResponse, Response},
     routing::get,
     Router,
 };
+use chamber::protocol::{ClientMsg, ServerMsg};
 use sailfish::TemplateOnce;
 use tokio::sync::broadcast::{self, error::RecvError};
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
 use tracing::instrument;
+use transport::{Transport, WsTransport};
+
+/// How many consecutive rate-limit violations a connection may rack up
+/// before it's closed with code 1008
+const RATE_LIMIT_VIOLATIONS_BEFORE_CLOSE: u32 = 5;
+
+/// How often `handle` wakes up on its own to drain the outbox and
+/// re-check the stall timer, independent of traffic from either the
+/// broadcast channel or the client. Without this, a client that's only
+/// receiving (never sending) would leave both checks stuck waiting on
+/// `ws.recv()` forever once the outbox hit the high watermark.
+const OUTBOUND_DRAIN_INTERVAL: Duration = Duration::from_millis(100);
 
 /// App state
 struct AppState {
     /// Counter
     cnt: AtomicU64,
-    /// Sender
-    snd: broadcast::Sender<(u64, Message)>,
+    /// Sender. The `u64` is the recipient: `u64::MAX` means "everyone",
+    /// any other id means "just that user" (used for whispers).
+    snd: broadcast::Sender<(u64, ServerMsg)>,
     /// Where to phone for the WebSocket
     wss: String,
+    /// Displayed nicknames, keyed by connection id. Entries are added on
+    /// [`ClientMsg::SetNick`] and removed on disconnect; a user without an
+    /// entry is displayed as their numeric id.
+    nicks: Mutex<HashMap<u64, String>>,
+    /// Maximum size, in bytes, of a fully reassembled message
+    max_message_size: usize,
+    /// Maximum size, in bytes, of a single frame (defaults to
+    /// [`AppState::max_message_size`] if not set)
+    max_frame_size: usize,
+    /// Sustained rate, in messages per second, allowed per connection
+    rate_limit_per_sec: f64,
+    /// Burst capacity (in messages) of the per-connection token bucket
+    rate_limit_burst: f64,
+    /// Cancelled once a shutdown signal is received; every `handle` loop
+    /// races this alongside its other events
+    shutdown: CancellationToken,
+    /// Tracks every spawned `handle` task. `axum`'s own upgrade machinery
+    /// spawns a connection's task outside of the hyper `Connection` future
+    /// that `Server::with_graceful_shutdown` waits on, so without this
+    /// `main` would return (and abort in-flight connections) as soon as
+    /// the shutdown signal fires rather than once they've actually
+    /// drained.
+    tracker: TaskTracker,
+    /// Outbox length at or below which a stalled connection is considered
+    /// recovered
+    outbound_low_watermark: usize,
+    /// Outbox length above which a connection stops pulling more
+    /// broadcast events and starts its stall timer
+    outbound_high_watermark: usize,
+    /// How long a connection's outbox may stay above the high watermark
+    /// before it's disconnected with code 1013
+    outbound_stall_timeout: Duration,
+}
+
+/// A simple token bucket, used to flood-protect a single connection.
+///
+/// Tokens refill continuously at `rate` per second, up to `burst`. Each
+/// message costs one token; when the bucket is empty, the message is
+/// refused.
+struct TokenBucket {
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: f64, burst: f64) -> Self {
+        TokenBucket {
+            rate,
+            burst,
+            tokens: burst,
+            last: Instant::now(),
+        }
+    }
+
+    /// Refill according to elapsed time, then try to spend one token.
+    /// Returns `true` if a token was available and spent.
+    fn try_take(&mut self) -> bool {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last).as_secs_f64();
+        self.last = now;
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.burst);
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Look up a user's displayed nickname, falling back to their numeric id.
+fn nick_of(state: &AppState, id: u64) -> String {
+    state
+        .nicks
+        .lock()
+        .unwrap()
+        .get(&id)
+        .cloned()
+        .unwrap_or_else(|| id.to_string())
+}
+
+/// Serialize and send a [`ServerMsg`] to this connection only.
+///
+/// Returns `false` if the send failed (the peer is presumably gone), in
+/// which case the caller should stop processing this connection rather
+/// than panicking it.
+async fn reply(ws: &mut impl Transport, msg: &ServerMsg) -> bool {
+    let text = serde_json::to_string(msg).expect("ServerMsg always serializes");
+    match ws.send(Message::Text(text)).await {
+        Ok(()) => true,
+        Err(e) => {
+            tracing::warn!("send failed, dropping connection: {}", e);
+            false
+        }
+    }
 }
 
 #[instrument(skip(ws, state))]
-async fn handle(mut ws: WebSocket, state: Arc<AppState>, id: u64) {
+async fn handle(mut ws: impl Transport, state: Arc<AppState>, id: u64) {
     enum Race {
-        Other(Result<(u64, Message), RecvError>),
+        Other(Result<(u64, ServerMsg), RecvError>),
         Me(Option<Result<Message, axum::Error>>),
+        Tick,
+        Shutdown,
     }
 
     tracing::info!("admitted");
 
-    ws.send(Message::Text(format!("You are {}", id)))
-        .await
-        .unwrap();
+    if !reply(&mut ws, &ServerMsg::Welcome { id }).await {
+        return;
+    }
 
     // Subscribe to the room
     let mut rcv = state.snd.subscribe();
 
+    // Let everyone (including future messages to ourselves) know we joined
+    state.snd.send((u64::MAX, ServerMsg::UserJoined { id })).unwrap();
+
+    // Flood protection: one token bucket per connection
+    let mut bucket = TokenBucket::new(state.rate_limit_per_sec, state.rate_limit_burst);
+    // Consecutive rate-limit violations; closes the connection past a threshold
+    let mut violations: u32 = 0;
+
+    // Messages drained from the broadcast channel but not yet written to
+    // this connection's socket. Buffering here, rather than leaving them
+    // in the shared `broadcast::channel`, means one slow client lags on
+    // its own queue instead of forcing every subscriber into
+    // `RecvError::Lagged`.
+    let mut outbox: VecDeque<ServerMsg> = VecDeque::new();
+    // Set once `outbox` first crosses the high watermark; cleared once it
+    // drops back to the low watermark. A connection stuck above the high
+    // watermark for longer than `outbound_stall_timeout` is disconnected.
+    let mut stalled_since: Option<Instant> = None;
+
+    // Drains the outbox and re-checks the stall timer on a schedule, so a
+    // client that's only receiving (never sending) still gets its queue
+    // drained and its stall timeout enforced instead of blocking forever
+    // on `ws.recv()`.
+    let mut drain_tick = tokio::time::interval(OUTBOUND_DRAIN_INTERVAL);
+    drain_tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
     loop {
-        // Competitively receive a message from either process
+        // Competitively receive a message from either process. Stop
+        // pulling more broadcast events once the outbox is full, so a
+        // slow connection's backlog is bounded by the watermark rather
+        // than growing without limit.
         let evt = tokio::select! {
-            v = rcv.recv() => Race::Other(v),
+            v = rcv.recv(), if outbox.len() < state.outbound_high_watermark => Race::Other(v),
             v = ws.recv() => Race::Me(v),
+            _ = drain_tick.tick() => Race::Tick,
+            _ = state.shutdown.cancelled() => Race::Shutdown,
         };
 
         match evt {
+            // Nothing to do but fall through to the outbox drain/stall
+            // check below
+            Race::Tick => (),
             // If closed, break
             Race::Other(Err(RecvError::Closed)) => break,
             // If lagged, log, ignore
             Race::Other(Err(RecvError::Lagged(skip))) => {
                 tracing::warn!("{} lagged {} messages", id, skip)
             }
-            // Special message (broadcast to all)
-            Race::Other(Ok((u64::MAX, msg))) => {
-                ws.send(msg).await.unwrap();
+            // Events for everyone, or addressed to this connection
+            // specifically (e.g. a whisper); queued rather than sent
+            // immediately, so a slow socket write doesn't stall draining
+            // the broadcast channel
+            Race::Other(Ok((target, msg))) if target == u64::MAX || target == id => {
+                outbox.push_back(msg);
             }
-            // Relay messages from others
-            Race::Other(Ok((id2, Message::Text(msg)))) => {
-                let msg = format!("{} says {}", id2, msg);
-                let msg = Message::Text(msg);
-                ws.send(msg).await.unwrap();
+            // Addressed to someone else; not for us
+            Race::Other(Ok(_)) => (),
+            // Shut down cleanly: tell the client, then close
+            Race::Shutdown => {
+                reply(&mut ws, &ServerMsg::Shutdown).await;
+                // 1001: going away
+                ws.close(1001, "server shutting down").await;
+                break;
             }
-            // Ignore binary
-            Race::Other(Ok((_, _))) => (),
             // Connection closed
             Race::Me(None) => break,
             // Handle errors
             Race::Me(Some(Err(e))) => tracing::error!("msg error {e:?}"),
-            // Say no to a message that's too large
-            Race::Me(Some(Ok(Message::Text(t)))) if t.len() > 500 => {
-                tracing::warn!("{} sent too long message", id);
-                // Warn but don't close
-                ws.send(Message::Text("message too long, not sent".to_string()))
-                    .await
-                    .unwrap();
-                // Wait
-                tokio::time::sleep(Duration::from_millis(500)).await;
+            // `WebSocketUpgrade::max_message_size`/`max_frame_size` (see
+            // `upgrade`) already stop tungstenite from ever reassembling
+            // more than `max_message_size` bytes, so this is a redundant
+            // belt-and-suspenders check against the fully-reassembled
+            // message -- and the only enforcement a future non-WebSocket
+            // `Transport` would have, since `Transport::recv` only ever
+            // hands back whole messages, never individual frames.
+            Race::Me(Some(Ok(Message::Text(t)))) if t.len() > state.max_message_size => {
+                tracing::warn!("{} exceeded max message size", id);
+                // 1009: message too big
+                ws.close(1009, "message too big").await;
+                break;
             }
-            // Send message to others
-            Race::Me(Some(Ok(msg @ Message::Text(_)))) => {
-                // The magic: send to the broadcast channel
-                state.snd.send((id, msg)).unwrap();
+            // Dispatch a command, subject to flood protection
+            Race::Me(Some(Ok(Message::Text(t)))) => {
+                if !bucket.try_take() {
+                    violations += 1;
+                    tracing::warn!("{} rate limited ({} in a row)", id, violations);
+
+                    if violations > RATE_LIMIT_VIOLATIONS_BEFORE_CLOSE {
+                        // 1008: policy violation
+                        ws.close(1008, "rate limited, slow down").await;
+                        break;
+                    }
+
+                    if !reply(
+                        &mut ws,
+                        &ServerMsg::Error {
+                            reason: "rate limited, slow down".into(),
+                        },
+                    )
+                    .await
+                    {
+                        break;
+                    }
+                    continue;
+                }
+                violations = 0;
+
+                match serde_json::from_str::<ClientMsg>(&t) {
+                    Ok(ClientMsg::Chat { body }) => {
+                        let nick = nick_of(&state, id);
+                        state
+                            .snd
+                            .send((u64::MAX, ServerMsg::Chat { from: id, nick, body }))
+                            .unwrap();
+                    }
+                    Ok(ClientMsg::SetNick { name }) => {
+                        state.nicks.lock().unwrap().insert(id, name);
+                    }
+                    Ok(ClientMsg::Whisper { to, body }) => {
+                        let nick = nick_of(&state, id);
+                        state
+                            .snd
+                            .send((to, ServerMsg::Chat { from: id, nick, body }))
+                            .unwrap();
+                    }
+                    Err(e) => {
+                        if !reply(
+                            &mut ws,
+                            &ServerMsg::Error {
+                                reason: format!("invalid command: {e}"),
+                            },
+                        )
+                        .await
+                        {
+                            break;
+                        }
+                    }
+                }
             }
             // Reject binary messages, close connection
             // Don't react to close messages---it may result in sending to a closed websocket
             Race::Me(Some(Ok(msg))) if !matches!(msg, Message::Close(_)) => {
                 tracing::warn!("{} sent binary", id);
-                let msg = CloseFrame {
-                    // 1003: unsupported data
-                    code: 1003,
-                    reason: "only text messages are allowed".into(),
-                };
-                ws.send(Message::Close(Some(msg))).await.unwrap();
+                // 1003: unsupported data
+                ws.close(1003, "only text messages are allowed").await;
                 // (connection will have been closed by now)
                 break;
             }
             Race::Me(_) => (),
         }
+
+        // Drain one queued message per tick towards the client
+        if let Some(msg) = outbox.pop_front() {
+            if !reply(&mut ws, &msg).await {
+                break;
+            }
+        }
+
+        // Track how long the outbox has stayed above the high watermark,
+        // and give up on a client that can't keep up
+        if outbox.len() > state.outbound_high_watermark {
+            let since = stalled_since.get_or_insert_with(Instant::now);
+            if since.elapsed() > state.outbound_stall_timeout {
+                tracing::warn!("{} outbound queue stalled, disconnecting", id);
+                // 1013: try again later
+                ws.close(1013, "try again later").await;
+                break;
+            }
+        } else if outbox.len() <= state.outbound_low_watermark {
+            stalled_since = None;
+        }
     }
 
-    // Send special message
-    state
-        .snd
-        .send((u64::MAX, Message::Text(format!("{} disconnected", id))))
-        .unwrap();
+    state.nicks.lock().unwrap().remove(&id);
+    state.snd.send((u64::MAX, ServerMsg::UserLeft { id })).unwrap();
     tracing::info!("{} disconnected", id);
 }
 
@@ -153,12 +432,24 @@ fn fail(e: axum::Error) {
 #[instrument(skip(ws, state))]
 #[debug_handler]
 async fn upgrade(ws: WebSocketUpgrade, State(state): State<Arc<AppState>>) -> Response {
-    ws.on_failed_upgrade(fail).on_upgrade(|socket| {
-        // SeqCst: ensure every thread agrees on the value of the counter
-        // Increment & get OLD value
-        let id = state.cnt.fetch_add(1, atomic::Ordering::SeqCst);
-        handle(socket, state, id)
-    })
+    // Bound reassembly at the protocol layer: a hostile client splitting an
+    // oversized payload across many continuation frames never gets them
+    // buffered in full, because tungstenite aborts the connection as soon
+    // as either cap is crossed, rather than handing `handle` a giant
+    // `Message::Text` after the fact.
+    ws.max_message_size(state.max_message_size)
+        .max_frame_size(state.max_frame_size)
+        .on_failed_upgrade(fail)
+        .on_upgrade(|socket| {
+            // SeqCst: ensure every thread agrees on the value of the counter
+            // Increment & get OLD value
+            let id = state.cnt.fetch_add(1, atomic::Ordering::SeqCst);
+            // `axum` spawns this future itself, outside of anything
+            // `Server::with_graceful_shutdown` tracks; track it on
+            // `state.tracker` instead so `main` can wait for it to finish.
+            let tracker = state.tracker.clone();
+            tracker.track_future(handle(WsTransport(socket), state, id))
+        })
 }
 
 /// Serve the index page
@@ -177,26 +468,130 @@ async fn index(State(state): State<Arc<AppState>>) -> Response {
     Html(index_html.render_once().unwrap()).into_response()
 }
 
-fn route() -> Router {
+/// Parse an environment variable as a `usize`, falling back to `default`
+/// if it's unset or unparseable.
+fn env_usize(key: &str, default: usize) -> usize {
+    std::env::var(key)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Parse an environment variable as an `f64`, falling back to `default`
+/// if it's unset or unparseable.
+fn env_f64(key: &str, default: f64) -> f64 {
+    std::env::var(key)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Parse an environment variable as a millisecond count, falling back to
+/// `default_ms` if it's unset or unparseable.
+fn env_duration_ms(key: &str, default_ms: u64) -> Duration {
+    Duration::from_millis(env_usize(key, default_ms as usize) as u64)
+}
+
+fn route(shutdown: CancellationToken, tracker: TaskTracker) -> Router {
     // Construct state
     let cnt = AtomicU64::new(0);
     let (snd, _rcv) = broadcast::channel(100);
     let wss = std::env::var("WSS").unwrap_or_else(|_| "ws://localhost:3000/ws".into());
+    let max_message_size = env_usize("MAX_MSG_BYTES", 500);
+    // Default the per-frame cap to the overall message cap when unset
+    let max_frame_size = env_usize("MAX_FRAME_BYTES", max_message_size);
+    let rate_limit_per_sec = env_f64("RATE_LIMIT_PER_SEC", 5.0);
+    let rate_limit_burst = env_f64("RATE_LIMIT_BURST", 10.0);
+    let outbound_low_watermark = env_usize("OUTBOUND_LOW_WATERMARK", 10);
+    let outbound_high_watermark = env_usize("OUTBOUND_HIGH_WATERMARK", 50);
+    let outbound_stall_timeout = env_duration_ms("OUTBOUND_STALL_TIMEOUT_MS", 5_000);
 
     tracing::info!("WSS phone: {}", wss);
+    tracing::info!(
+        "max message size: {} bytes, max frame size: {} bytes",
+        max_message_size,
+        max_frame_size
+    );
+    tracing::info!(
+        "rate limit: {} msg/s, burst {}",
+        rate_limit_per_sec,
+        rate_limit_burst
+    );
+    tracing::info!(
+        "outbound queue: low watermark {}, high watermark {}, stall timeout {:?}",
+        outbound_low_watermark,
+        outbound_high_watermark,
+        outbound_stall_timeout
+    );
 
     Router::new()
         .route("/", get(index))
         .route("/ws", get(upgrade))
-        .with_state(Arc::new(AppState { cnt, snd, wss }))
+        .with_state(Arc::new(AppState {
+            cnt,
+            snd,
+            wss,
+            nicks: Mutex::new(HashMap::new()),
+            max_message_size,
+            max_frame_size,
+            rate_limit_per_sec,
+            rate_limit_burst,
+            shutdown,
+            tracker,
+            outbound_low_watermark,
+            outbound_high_watermark,
+            outbound_stall_timeout,
+        }))
+}
+
+/// Resolves once SIGINT or SIGTERM is received, then cancels `token` so
+/// every `handle` loop and the accept loop wind down.
+async fn shutdown_signal(token: CancellationToken) {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+
+    tracing::info!("shutdown signal received, draining connections");
+    token.cancel();
 }
 
 #[tokio::main]
 async fn main() {
     tracing_subscriber::fmt::init();
     let bind: std::net::SocketAddr = "0.0.0.0:3000".parse().unwrap();
-    let r = route();
-    let s = axum::Server::bind(&bind).serve(r.into_make_service());
+    let shutdown = CancellationToken::new();
+    let tracker = TaskTracker::new();
+    let r = route(shutdown.clone(), tracker.clone());
+    let s = axum::Server::bind(&bind)
+        .serve(r.into_make_service())
+        .with_graceful_shutdown(shutdown_signal(shutdown));
     tracing::info!("Greetings from {}", bind);
     s.await.unwrap();
+
+    // `s.await` only waits for hyper's `Connection` futures, which for a
+    // WebSocket complete as soon as the 101 upgrade response is sent; the
+    // actual per-connection `handle` tasks are tracked separately (see
+    // `AppState::tracker`), so wait for those too before the runtime tears
+    // down.
+    tracker.close();
+    tracker.wait().await;
+    tracing::info!("all connections drained, exiting");
 }
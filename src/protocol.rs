@@ -0,0 +1,37 @@
+//! The typed JSON protocol spoken over a chamber's wire transport.
+//!
+//! Clients send a [`ClientMsg`] and the chamber replies with one or more
+//! [`ServerMsg`]s, either directly to the sender or broadcast to the room.
+
+use serde::{Deserialize, Serialize};
+
+/// A command sent by a client.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ClientMsg {
+    /// Speak to everyone in the room.
+    Chat { body: String },
+    /// Change the sender's displayed nickname.
+    SetNick { name: String },
+    /// Send a private message to a single other user.
+    Whisper { to: u64, body: String },
+}
+
+/// An event sent from the chamber to a client.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ServerMsg {
+    /// Sent once, right after a connection is admitted.
+    Welcome { id: u64 },
+    /// A chat message, either broadcast to the room or whispered.
+    Chat { from: u64, nick: String, body: String },
+    /// Another user joined the room.
+    UserJoined { id: u64 },
+    /// A user left the room.
+    UserLeft { id: u64 },
+    /// The client's last command couldn't be honored.
+    Error { reason: String },
+    /// The server is shutting down; the connection will be closed
+    /// immediately after this is sent.
+    Shutdown,
+}
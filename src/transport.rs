@@ -0,0 +1,48 @@
+//! Abstraction over the wire protocol a connection speaks.
+//!
+//! [`crate::handle`] only ever sends and receives [`Message`]s and closes
+//! the connection with a code and reason; it never touches
+//! `axum::extract::ws` directly. That means a second backend (for example,
+//! a WebTransport bidirectional-stream implementation) can be registered
+//! on its own route without changing the chamber's broadcast/relay logic
+//! at all.
+
+use async_trait::async_trait;
+use axum::extract::ws::{CloseFrame, Message, WebSocket};
+
+/// A bidirectional, message-oriented connection to a single client.
+#[async_trait]
+pub trait Transport: Send {
+    /// Send one message to the peer.
+    async fn send(&mut self, msg: Message) -> Result<(), axum::Error>;
+
+    /// Receive the next message, or `None` once the connection is closed.
+    async fn recv(&mut self) -> Option<Result<Message, axum::Error>>;
+
+    /// Close the connection with the given WebSocket-style close code and
+    /// reason. Best-effort: errors are swallowed, since there's nothing
+    /// more to do with a peer that's already gone.
+    async fn close(&mut self, code: u16, reason: &str);
+}
+
+/// [`Transport`] backed by an axum WebSocket.
+pub struct WsTransport(pub WebSocket);
+
+#[async_trait]
+impl Transport for WsTransport {
+    async fn send(&mut self, msg: Message) -> Result<(), axum::Error> {
+        self.0.send(msg).await
+    }
+
+    async fn recv(&mut self) -> Option<Result<Message, axum::Error>> {
+        self.0.recv().await
+    }
+
+    async fn close(&mut self, code: u16, reason: &str) {
+        let frame = CloseFrame {
+            code,
+            reason: reason.to_string().into(),
+        };
+        let _ = self.0.send(Message::Close(Some(frame))).await;
+    }
+}